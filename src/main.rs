@@ -17,8 +17,12 @@ USAGE:
     sf.exe [FLAGS] [path] [SUBCOMMAND]
 
 FLAGS:
+    -c, --count      print distinct lines alongside their occurrence count
+        --groups     treat blank-line-delimited records as the sets for
+                     intersect/union, instead of whole files
     -h, --help       Prints help information
     -r, --reverse    take only repeated items
+        --sort       with --count, order output by descending count
     -V, --version    Prints version information
 
 ARGS:
@@ -26,18 +30,22 @@ ARGS:
 
 SUBCOMMANDS:
     diff         set difference
+    filter       keep (or, with --invert, drop) lines matching any pattern
     help         Prints this message or the help of the given subcommand(s)
     intersect    set intersection
+    symmetric    symmetric difference
+    union        set union
 ```
 */
 
 use std::{
     fmt::Display,
     fs,
-    io::{self, Read, Write},
+    io::{self, BufRead, Read, Write},
 };
 
-use hashbrown::HashSet;
+use aho_corasick::AhoCorasick;
+use hashbrown::{HashMap, HashSet};
 use structopt::StructOpt;
 
 #[derive(Clone, Debug, StructOpt)]
@@ -47,6 +55,16 @@ struct Opts {
     /// take only repeated items
     #[structopt(short, long)]
     reverse: bool,
+    /// print distinct lines alongside their occurrence count
+    #[structopt(short, long)]
+    count: bool,
+    /// with --count, order output by descending count
+    #[structopt(long)]
+    sort: bool,
+    /// treat blank-line-delimited records as the sets for intersect/union,
+    /// instead of whole files
+    #[structopt(long)]
+    groups: bool,
 
     #[structopt(subcommand)]
     command: Option<Command>,
@@ -58,16 +76,44 @@ enum Command {
     Diff(Diff),
     /// set intersection
     Intersect(Intersect),
+    /// set union
+    Union(Union),
+    /// symmetric difference
+    Symmetric(Symmetric),
+    /// keep (or, with --invert, drop) lines matching any pattern
+    Filter(Filter),
 }
 
 #[derive(Clone, Debug, StructOpt)]
 struct Diff {
-    pub path: String,
+    pub path: Vec<String>,
 }
 
 #[derive(Clone, Debug, StructOpt)]
 struct Intersect {
-    pub path: String,
+    pub path: Vec<String>,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+struct Union {
+    pub path: Vec<String>,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+struct Symmetric {
+    pub path: Vec<String>,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+struct Filter {
+    /// literal patterns to match against each line
+    pub pattern: Vec<String>,
+    /// read additional newline-delimited patterns from a file
+    #[structopt(long)]
+    pub patterns_file: Option<String>,
+    /// keep lines that do NOT match any pattern
+    #[structopt(short, long)]
+    pub invert: bool,
 }
 
 trait WithOpts {
@@ -88,44 +134,206 @@ fn main() {
 }
 
 fn run(opts: &Opts) -> io::Result<()> {
+    if opts.groups {
+        return match &opts.command {
+            Some(Command::Intersect(_)) => {
+                print_groups(opts, |a, b| a.intersection(b).copied().collect())
+            }
+            Some(Command::Union(_)) => print_groups(opts, |a, b| a.union(b).copied().collect()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--groups requires the `intersect` or `union` subcommand",
+            )),
+        };
+    }
+
     match &opts.command {
         Some(Command::Diff(Diff { path })) => print_difference(opts, path),
-        Some(Command::Intersect(Intersect { path })) => print_intersection(opts, &path),
+        Some(Command::Intersect(Intersect { path })) => print_intersection(opts, path),
+        Some(Command::Union(Union { path })) => print_union(opts, path),
+        Some(Command::Symmetric(Symmetric { path })) => print_symmetric_difference(opts, path),
+        Some(Command::Filter(filter)) => print_filter(opts, filter),
+        None if opts.count => print_count(opts),
         None => print_unique(opts),
     }
 }
 
-fn print_difference(opts: &Opts, path: &str) -> io::Result<()> {
+fn print_difference(opts: &Opts, paths: &[String]) -> io::Result<()> {
+    require_paths(paths)?;
+    let compares = read_comparison_sets(paths)?;
+    stream_filtered(opts, |line| !compares.iter().any(|set| set.contains(line)))
+}
+
+fn print_intersection(opts: &Opts, paths: &[String]) -> io::Result<()> {
+    require_paths(paths)?;
+    let compares = read_comparison_sets(paths)?;
+    stream_filtered(opts, |line| compares.iter().all(|set| set.contains(line)))
+}
+
+fn require_paths(paths: &[String]) -> io::Result<()> {
+    if paths.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "expected at least one comparison path",
+        ));
+    }
+    Ok(())
+}
+
+fn print_union(opts: &Opts, paths: &[String]) -> io::Result<()> {
     let text = read_text(opts)?;
-    let compare = fs::read_to_string(path)?;
-    let a: HashSet<_> = text.lines().collect();
-    let b: HashSet<_> = compare.lines().collect();
-    let difference = a.difference(&b);
-    format(difference)
+    let compares = read_comparisons(paths)?;
+    let base: HashSet<_> = text.lines().collect();
+    let result = fold_sets(base, &compares, |a, b| a.union(b).copied().collect());
+    format(result)
 }
 
-fn print_intersection(opts: &Opts, path: &str) -> io::Result<()> {
+fn print_symmetric_difference(opts: &Opts, paths: &[String]) -> io::Result<()> {
     let text = read_text(opts)?;
-    let compare = fs::read_to_string(path)?;
-    let a: HashSet<_> = text.lines().collect();
-    let b: HashSet<_> = compare.lines().collect();
-    let intersection = a.intersection(&b);
-    format(intersection)
+    let compares = read_comparisons(paths)?;
+    let base: HashSet<_> = text.lines().collect();
+    let result = fold_sets(base, &compares, |a, b| {
+        a.symmetric_difference(b).copied().collect()
+    });
+    format(result)
 }
 
-fn print_unique(opts: &Opts) -> io::Result<()> {
+/// Runs each line through a single prebuilt Aho-Corasick automaton.
+fn print_filter(opts: &Opts, filter: &Filter) -> io::Result<()> {
+    let patterns = read_patterns(filter)?;
+    let automaton =
+        AhoCorasick::new(&patterns).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let reader = open_base(opts)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in reader.lines() {
+        let line = line?;
+        if automaton.is_match(&line) != filter.invert {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_patterns(filter: &Filter) -> io::Result<Vec<String>> {
+    let mut patterns = filter.pattern.clone();
+    if let Some(path) = &filter.patterns_file {
+        let reader = io::BufReader::new(fs::File::open(path)?);
+        for line in reader.lines() {
+            patterns.push(line?);
+        }
+    }
+    // an empty pattern would match every line, so drop stray blank entries
+    patterns.retain(|pattern| !pattern.is_empty());
+    if patterns.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "filter requires at least one non-empty pattern",
+        ));
+    }
+    Ok(patterns)
+}
+
+/// Splits the base set on blank lines and folds the per-group sets together.
+fn print_groups(
+    opts: &Opts,
+    op: impl Fn(&HashSet<&str>, &HashSet<&str>) -> HashSet<&str>,
+) -> io::Result<()> {
     let text = read_text(opts)?;
+    let mut groups = text
+        .split("\n\n")
+        .filter(|group| !group.is_empty())
+        .map(|group| group.lines().collect::<HashSet<_>>());
+    let first = match groups.next() {
+        Some(group) => group,
+        None => return Ok(()),
+    };
+    let result = groups.fold(first, |acc, group| op(&acc, &group));
+    format(result)
+}
+
+fn read_comparisons(paths: &[String]) -> io::Result<Vec<String>> {
+    paths.iter().map(fs::read_to_string).collect()
+}
+
+/// Reads each comparison path into its own `HashSet`.
+fn read_comparison_sets(paths: &[String]) -> io::Result<Vec<HashSet<String>>> {
+    paths
+        .iter()
+        .map(|path| io::BufReader::new(fs::File::open(path)?).lines().collect())
+        .collect()
+}
+
+/// Writes each line that passes `keep` and hasn't already been written.
+fn stream_filtered(opts: &Opts, keep: impl Fn(&str) -> bool) -> io::Result<()> {
+    let reader = open_base(opts)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut seen = HashSet::new();
+    for line in reader.lines() {
+        let line = line?;
+        if keep(&line) && seen.insert(line.clone()) {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Folds `base` across each comparison set in `compares` left-to-right,
+/// applying `op` at each step.
+fn fold_sets<'a>(
+    base: HashSet<&'a str>,
+    compares: &'a [String],
+    op: impl Fn(&HashSet<&'a str>, &HashSet<&'a str>) -> HashSet<&'a str>,
+) -> HashSet<&'a str> {
+    compares.iter().fold(base, |acc, compare| {
+        let b: HashSet<&str> = compare.lines().collect();
+        op(&acc, &b)
+    })
+}
+
+fn print_unique(opts: &Opts) -> io::Result<()> {
+    let reader = open_base(opts)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
     let mut a = HashSet::new();
     if opts.reverse {
         let mut b = HashSet::new();
-        let repeated = text
-            .lines()
-            .filter(|&value| !a.insert(value) && b.insert(value));
-        format(repeated)
+        for line in reader.lines() {
+            let line = line?;
+            if !a.insert(line.clone()) && b.insert(line.clone()) {
+                writeln!(out, "{}", line)?;
+            }
+        }
     } else {
-        let unique = text.lines().filter(|&value| a.insert(value));
-        format(unique)
+        for line in reader.lines() {
+            let line = line?;
+            if a.insert(line.clone()) {
+                writeln!(out, "{}", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_count(opts: &Opts) -> io::Result<()> {
+    let reader = open_base(opts)?;
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for line in reader.lines() {
+        *counts.entry(line?).or_insert(0) += 1;
     }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    if opts.sort {
+        counts.sort_by(|a, b| b.1.cmp(&a.1));
+    }
+
+    format(
+        counts
+            .into_iter()
+            .map(|(line, count)| format!("{}\t{}", count, line)),
+    )
 }
 
 #[inline]
@@ -147,3 +355,11 @@ fn read_text(opts: &Opts) -> io::Result<String> {
         }
     }
 }
+
+/// Opens the base set for incremental, line-at-a-time reading.
+fn open_base(opts: &Opts) -> io::Result<Box<dyn BufRead>> {
+    match &opts.path {
+        Some(path) => Ok(Box::new(io::BufReader::new(fs::File::open(path)?))),
+        None => Ok(Box::new(io::BufReader::new(io::stdin()))),
+    }
+}